@@ -1,21 +1,47 @@
 extern crate rand;
-
-#[derive(Clone)]
-struct Daisy {
-    albedo: f64,
-    phenotype_volatility: f64,
+extern crate rand_chacha;
+extern crate rand_distr;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate bincode;
+extern crate serde_json;
+extern crate image;
+
+mod population;
+mod render;
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use population::Population;
+use rand_distr::{Distribution, Normal};
+
+// small Gaussian step used to mutate phenotype_volatility itself, so the
+// mutation rate can drift across generations instead of staying frozen
+const VOLATILITY_STEP_STD_DEV: f64 = 0.025;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Daisy {
+    pub(crate) albedo: f64,
+    pub(crate) phenotype_volatility: f64,
 }
 
 impl Daisy {
 
-    fn black() -> Daisy {
+    pub(crate) fn black() -> Daisy {
         Daisy{
             albedo: 0.25,
             phenotype_volatility: 0.05,
         }
     }
 
-    fn white() -> Daisy {
+    pub(crate) fn white() -> Daisy {
         Daisy{
             albedo: 0.75,
             phenotype_volatility: 0.05,
@@ -32,42 +58,63 @@ impl Daisy {
         }
     }
 
-    fn offspring(&self) -> Daisy {
+    pub(crate) fn offspring(&self, rng: &mut ChaCha8Rng) -> Daisy {
         let new_albedo = self.albedo +
-            self.phenotype_volatility * (rand::random::<f64>() - 0.5);
+            self.phenotype_volatility * (rng.gen::<f64>() - 0.5);
+        let volatility_step = Normal::new(0.0, VOLATILITY_STEP_STD_DEV).unwrap().sample(rng);
 
         Daisy{
-            albedo: if new_albedo < 0.0 { 0.0 }
-                    else if new_albedo > 1.0 { 1.0 }
-                    else { new_albedo },
-            phenotype_volatility: self.phenotype_volatility,
+            albedo: new_albedo.clamp(0.0, 1.0),
+            phenotype_volatility: (self.phenotype_volatility + volatility_step).clamp(0.0, 0.5),
+        }
+    }
+
+    // sexual reproduction: each gene drawn from one parent or the other,
+    // then mutated the same way `offspring` mutates a clone
+    pub(crate) fn crossover(&self, other: &Daisy, rng: &mut ChaCha8Rng) -> Daisy {
+        let albedo = if rng.gen::<bool>() { self.albedo } else { other.albedo };
+        let phenotype_volatility = if rng.gen::<bool>() {
+            self.phenotype_volatility
+        } else {
+            other.phenotype_volatility
+        };
+
+        let new_albedo = albedo + phenotype_volatility * (rng.gen::<f64>() - 0.5);
+        let volatility_step = Normal::new(0.0, VOLATILITY_STEP_STD_DEV).unwrap().sample(rng);
+
+        Daisy{
+            albedo: new_albedo.clamp(0.0, 1.0),
+            phenotype_volatility: (phenotype_volatility + volatility_step).clamp(0.0, 0.5),
         }
     }
 
 }
 
-#[derive(Clone)]
-struct World {
-    dim: (usize, usize),
-    daisies: Vec<Option<Daisy>>,
-    death_rate: f64,
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct World {
+    pub(crate) dim: (usize, usize),
+    pub(crate) daisies: Vec<Option<Daisy>>,
+    pub(crate) death_rate: f64,
+    pub(crate) luminosity: f64,
+    rng: ChaCha8Rng,
 }
 
 impl World {
 
-    fn size(&self) -> usize {
+    pub(crate) fn size(&self) -> usize {
         self.dim.0 * self.dim.1
     }
 
-    fn iter(&self) -> WorldIterator {
+    #[allow(dead_code)]
+    fn iter(&self) -> WorldIterator<'_> {
         WorldIterator{
             pos: 0,
-            world: &self,
+            world: self,
         }
     }
 
     fn at(&self, i: usize) -> Option<&Daisy> {
-        if (i >= 0) && (i < self.size()) {
+        if i < self.size() {
             self.daisies[i].as_ref()
         } else {
             None
@@ -106,7 +153,7 @@ impl World {
         }
     }
 
-    fn albedo(&self) -> f64 {
+    pub(crate) fn albedo(&self) -> f64 {
         self.daisies.iter()
             .map(|daisy_opt| daisy_opt.as_ref()
                  .map(|d| d.albedo)
@@ -114,11 +161,10 @@ impl World {
             .sum::<f64>() / (self.size() as f64)
     }
 
-    fn temperature_field(&self) -> Vec<f64> {
+    pub(crate) fn temperature_field(&self) -> Vec<f64> {
         let q = 0.125; // neightbour diffusivity
         const S: f64 = 917.0; // solar insolation
         const SB: f64 = 5.67e-8;
-        const L: f64 = 1.0; // luminosity
 
         (0..self.size())
             .map(|i| (1.0 - 4.0*q) * self.at(i).map(|d| d.albedo).unwrap_or(0.5) +
@@ -126,14 +172,27 @@ impl World {
                     self.right_of(i).map(|d| d.albedo).unwrap_or(0.5) +
                     self.left_of(i).map(|d| d.albedo).unwrap_or(0.5) +
                     self.below(i).map(|d| d.albedo).unwrap_or(0.5)))
-            .map(|albedo| (S * L / SB * (1.0 - albedo)).powf(0.25))
+            .map(|albedo| (S * self.luminosity / SB * (1.0 - albedo)).powf(0.25))
             .collect()
     }
 
-    fn new_randomized(dim: (usize, usize), death_rate: f64) -> World {
+    pub(crate) fn mean_temperature(&self) -> f64 {
+        let tfield = self.temperature_field();
+        tfield.iter().sum::<f64>() / (tfield.len() as f64)
+    }
 
-        fn new_daisy_opt() -> Option<Daisy> {
-            let r= rand::random::<f64>();
+    pub(crate) fn live_count(&self) -> usize {
+        self.daisies.iter().filter(|d| d.is_some()).count()
+    }
+
+    pub(crate) fn new_randomized(dim: (usize, usize), death_rate: f64) -> World {
+        World::new_randomized_seeded(dim, death_rate, rand::random::<u64>())
+    }
+
+    pub(crate) fn new_randomized_seeded(dim: (usize, usize), death_rate: f64, seed: u64) -> World {
+
+        fn new_daisy_opt(rng: &mut ChaCha8Rng) -> Option<Daisy> {
+            let r = rng.gen::<f64>();
             if r < 0.1 {
                 Some(Daisy::black())
             } else if r < 0.2 {
@@ -143,14 +202,41 @@ impl World {
             }
         }
 
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let daisies = (0..(dim.0*dim.1)).map(|_| new_daisy_opt(&mut rng)).collect();
+
         World{
-            dim: dim.clone(),
-            daisies: (0..(dim.0*dim.1)).map(|_| new_daisy_opt()).collect(),
-            death_rate: death_rate,
+            dim,
+            daisies,
+            death_rate,
+            luminosity: 1.0,
+            rng,
         }
     }
 
-    fn iterate(&self) -> World {
+    // construct a world from an explicit daisy grid, e.g. one perturbed
+    // from an earlier generation's layout by `population`
+    pub(crate) fn with_daisies(dim: (usize, usize), daisies: Vec<Option<Daisy>>,
+                               death_rate: f64, seed: u64) -> World {
+        World{
+            dim,
+            daisies,
+            death_rate,
+            luminosity: 1.0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    // `i` indexes both `tfield` and the grid-neighbour lookups below, so a
+    // plain iterator over `tfield` wouldn't simplify this loop.
+    //
+    // note: as of the crossover change, every one of the 4 neighbours is
+    // tested against reproduce_prob (previously only a single randomly
+    // chosen neighbour was tested per step), so reproduction now fires
+    // more often and trajectories are not comparable to pre-crossover runs
+    // even with the same seed.
+    #[allow(clippy::needless_range_loop)]
+    pub(crate) fn iterate(&self) -> World {
 
         let mut new_world = self.clone();
         let tfield: Vec<f64> = self.temperature_field();
@@ -164,38 +250,37 @@ impl World {
 
             let temp = tfield[i];
 
-            // choose a neighbour at random
-            let r: f64 = rand::random();
-            let neighbour = if r < 0.25 {
-                self.above(i)
-            } else if r < 0.5 {
-                self.right_of(i)
-            } else if r < 0.75 {
-                self.below(i)
-            } else {
-                self.left_of(i)
-            };
-
-            let prob = match neighbour {
-                Some(_) => neighbour.unwrap().reproduce_prob(temp),
-                None => 0.0
-            };
+            // collect neighbours that pass the reproduction test
+            let neighbours = [self.above(i), self.right_of(i), self.below(i), self.left_of(i)];
+            let mut passing: Vec<&Daisy> = Vec::with_capacity(4);
+            for neighbour in neighbours.iter().filter_map(|n| *n) {
+                if new_world.rng.gen::<f64>() < neighbour.reproduce_prob(temp) {
+                    passing.push(neighbour);
+                }
+            }
 
-            if rand::random::<f64>() < prob {
-                new_world.daisies[i] = neighbour.map(|d| d.offspring());
+            if passing.len() >= 2 {
+                let a = new_world.rng.gen_range(0..passing.len());
+                let mut b = new_world.rng.gen_range(0..passing.len());
+                while b == a {
+                    b = new_world.rng.gen_range(0..passing.len());
+                }
+                new_world.daisies[i] = Some(passing[a].crossover(passing[b], &mut new_world.rng));
+            } else if let Some(parent) = passing.first() {
+                new_world.daisies[i] = Some(parent.offspring(&mut new_world.rng));
             }
         }
 
         // die according to global death rate
         for i in 0..self.size() {
-            if new_world.daisies[i].is_some() && (rand::random::<f64>() < self.death_rate) {
+            if new_world.daisies[i].is_some() && (new_world.rng.gen::<f64>() < self.death_rate) {
                 new_world.daisies[i] = None;
             }
         }
         new_world
     }
 
-    fn print_stats(&self) -> () {
+    fn print_stats(&self) {
         let count_empty = self.daisies.iter()
             .filter(|d| d.is_none())
             .map(|_| 1_usize)
@@ -206,8 +291,35 @@ impl World {
         println!("empty cells: {}", count_empty);
         println!("planetary albedo: {}", planetary_albedo);
     }
+
+    // binary snapshot, including the RNG state
+    pub(crate) fn save(&self, path: &str) -> io::Result<()> {
+        let file = File::create(path)?;
+        bincode::serialize_into(file, self)
+            .map_err(io::Error::other)
+    }
+
+    pub(crate) fn load(path: &str) -> io::Result<World> {
+        let file = File::open(path)?;
+        bincode::deserialize_from(file)
+            .map_err(io::Error::other)
+    }
+
+    // human-readable counterpart to save/load
+    fn save_json(&self, path: &str) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(io::Error::other)
+    }
+
+    fn load_json(path: &str) -> io::Result<World> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(io::Error::other)
+    }
 }
 
+#[allow(dead_code)]
 struct WorldIterator<'a> {
     pos: usize,
     world: &'a World,
@@ -225,14 +337,133 @@ impl<'a> Iterator for WorldIterator<'a> {
 
 
 
+// write (luminosity, planetary_albedo, mean_temperature, live_count) rows
+// to a CSV file for plotting the hysteresis loop
+fn export_timeseries_csv(records: &[(f64, f64, f64, usize)], path: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "luminosity,planetary_albedo,mean_temperature,live_count")?;
+    for &(luminosity, albedo, mean_temperature, live_count) in records {
+        writeln!(file, "{},{},{},{}", luminosity, albedo, mean_temperature, live_count)?;
+    }
+    Ok(())
+}
+
 fn main() {
 
     println!("it compiled!");
     let mut world = World::new_randomized((20, 20), 0.3);
 
-    for timestep in 0..30 {
+    // ramp luminosity up then back down to reproduce the classic
+    // Daisyworld hysteresis loop
+    let steps = 60;
+    let l_min = 0.6;
+    let l_max = 1.4;
+    let mut records: Vec<(f64, f64, f64, usize)> = Vec::with_capacity(steps);
+
+    for timestep in 0..steps {
+        let phase = timestep as f64 / (steps as f64 - 1.0);
+        world.luminosity = if phase < 0.5 {
+            l_min + phase * 2.0 * (l_max - l_min)
+        } else {
+            l_min + (1.0 - phase) * 2.0 * (l_max - l_min)
+        };
+
         world = world.iterate();
         world.print_stats();
+
+        if let Err(e) = world.render_albedo(&format!("frame_albedo_{:03}.png", timestep)) {
+            eprintln!("failed to render albedo frame: {}", e);
+        }
+        if let Err(e) = world.render_temperature(&format!("frame_temperature_{:03}.png", timestep)) {
+            eprintln!("failed to render temperature frame: {}", e);
+        }
+
+        records.push((world.luminosity, world.albedo(), world.mean_temperature(), world.live_count()));
+    }
+
+    if let Err(e) = export_timeseries_csv(&records, "hysteresis.csv") {
+        eprintln!("failed to write timeseries csv: {}", e);
+    }
+
+    if let Err(e) = world.save("snapshot.bin") {
+        eprintln!("failed to save snapshot: {}", e);
+    } else {
+        match World::load("snapshot.bin") {
+            Ok(reloaded) => println!("reloaded snapshot, planetary albedo {}", reloaded.albedo()),
+            Err(e) => eprintln!("failed to load snapshot: {}", e),
+        }
+    }
+
+    if let Err(e) = world.save_json("snapshot.json") {
+        eprintln!("failed to save snapshot json: {}", e);
+    } else if let Err(e) = World::load_json("snapshot.json") {
+        eprintln!("failed to load snapshot json: {}", e);
+    }
+
+    // evolve initial daisy distributions across a small population
+    let mut population = Population::new(8, (20, 20));
+    for generation in 0..5 {
+        population.step_generation(30, |w| w.albedo());
+        if let Some(best) = population.best_world() {
+            println!("generation {}: best planetary albedo {}", generation, best.albedo());
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // chunk0-1's whole point is that two runs are no longer uncomparable:
+    // the same seed must produce the same trajectory.
+    #[test]
+    fn seeded_worlds_are_deterministic() {
+        let mut a = World::new_randomized_seeded((6, 6), 0.3, 42);
+        let mut b = World::new_randomized_seeded((6, 6), 0.3, 42);
+
+        for _ in 0..5 {
+            a = a.iterate();
+            b = b.iterate();
+        }
+
+        assert!(a.daisies == b.daisies);
+    }
+
+    // chunk0-2's whole point is that a reloaded world continues
+    // deterministically, i.e. the RNG state round-trips too.
+    #[test]
+    fn save_load_round_trip_continues_deterministically() {
+        let mut direct = World::new_randomized_seeded((6, 6), 0.3, 7);
+        for _ in 0..3 {
+            direct = direct.iterate();
+        }
+
+        let path = std::env::temp_dir().join("daisyworld_test_snapshot.bin");
+        direct.save(path.to_str().unwrap()).unwrap();
+        let mut reloaded = World::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let mut reference = direct;
+        for _ in 0..3 {
+            reference = reference.iterate();
+            reloaded = reloaded.iterate();
+        }
 
+        assert!(reference.daisies == reloaded.daisies);
+    }
+
+    // crossover should always produce genes blended from the two parents
+    // (plus a bounded mutation step), never something out of range.
+    #[test]
+    fn crossover_stays_within_valid_ranges() {
+        let black = Daisy::black();
+        let white = Daisy::white();
+        let mut rng = ChaCha8Rng::seed_from_u64(123);
+
+        for _ in 0..200 {
+            let child = black.crossover(&white, &mut rng);
+            assert!((0.0..=1.0).contains(&child.albedo));
+            assert!((0.0..=0.5).contains(&child.phenotype_volatility));
+        }
+    }
 }
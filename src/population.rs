@@ -0,0 +1,144 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use std::cmp::Ordering;
+
+use crate::{Daisy, World};
+
+// an ensemble of Worlds bred across generations by fitness-proportionate
+// selection, so a run's initial daisy distribution can be evolved instead
+// of hand-tuned
+pub(crate) struct Population {
+    worlds: Vec<World>,
+    best_world: Option<World>,
+    death_rate: f64,
+    rng: ChaCha8Rng,
+}
+
+impl Population {
+
+    pub(crate) fn new(size: usize, dim: (usize, usize)) -> Population {
+        Population::new_seeded(size, dim, rand::random::<u64>())
+    }
+
+    pub(crate) fn new_seeded(size: usize, dim: (usize, usize), seed: u64) -> Population {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let death_rate = 0.3;
+        let worlds = (0..size)
+            .map(|_| World::new_randomized_seeded(dim, death_rate, rng.gen::<u64>()))
+            .collect();
+
+        Population{
+            worlds,
+            best_world: None,
+            death_rate,
+            rng,
+        }
+    }
+
+    pub(crate) fn best_world(&self) -> Option<&World> {
+        self.best_world.as_ref()
+    }
+
+    // run every member forward `timesteps` iterations, score it with
+    // `fitness`, then breed the next generation: the top decile survive
+    // as elites, and the rest are filled by roulette-wheel selection over
+    // the current generation's initial conditions, perturbed
+    pub(crate) fn step_generation<F>(&mut self, timesteps: usize, fitness: F)
+    where
+        F: Fn(&World) -> f64,
+    {
+        let evaluated: Vec<World> = self.worlds.iter()
+            .map(|world| {
+                let mut w = world.clone();
+                for _ in 0..timesteps {
+                    w = w.iterate();
+                }
+                w
+            })
+            .collect();
+
+        let scores: Vec<f64> = evaluated.iter().map(fitness).collect();
+        let total_fitness: f64 = scores.iter().sum();
+
+        let mut ranked: Vec<usize> = (0..self.worlds.len()).collect();
+        ranked.sort_by(|&a, &b| {
+            scores[b].partial_cmp(&scores[a]).unwrap_or(Ordering::Equal)
+        });
+
+        self.best_world = Some(evaluated[ranked[0]].clone());
+
+        let n_elites = (self.worlds.len() / 10).max(1);
+        let mut next_gen: Vec<World> = ranked.iter()
+            .take(n_elites)
+            .map(|&i| self.worlds[i].clone())
+            .collect();
+
+        while next_gen.len() < self.worlds.len() {
+            let parent = if total_fitness > 0.0 {
+                Population::select_roulette(&mut self.rng, &self.worlds, &scores, total_fitness)
+            } else {
+                &self.worlds[ranked[self.rng.gen_range(0..n_elites)]]
+            };
+            let seed = self.rng.gen::<u64>();
+            next_gen.push(Population::perturb(parent, self.death_rate, seed));
+        }
+
+        self.worlds = next_gen;
+    }
+
+    // draw a cumulative-sum threshold over 0..total_fitness and return the
+    // first world whose running fitness total crosses it
+    fn select_roulette<'a>(rng: &mut ChaCha8Rng, worlds: &'a [World], scores: &[f64],
+                            total_fitness: f64) -> &'a World {
+        let threshold = rng.gen::<f64>() * total_fitness;
+        let mut cumulative = 0.0;
+        for (world, &score) in worlds.iter().zip(scores.iter()) {
+            cumulative += score;
+            if cumulative >= threshold {
+                return world;
+            }
+        }
+        worlds.last().unwrap()
+    }
+
+    // perturb a parent's initial daisy distribution: mutate a fraction of
+    // living daisies and seed a few new ones into empty cells
+    fn perturb(parent: &World, death_rate: f64, seed: u64) -> World {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let daisies = parent.daisies.iter()
+            .map(|daisy_opt| match daisy_opt {
+                Some(daisy) if rng.gen::<f64>() < 0.1 => Some(daisy.offspring(&mut rng)),
+                Some(daisy) => Some(daisy.clone()),
+                None if rng.gen::<f64>() < 0.02 => {
+                    Some(if rng.gen::<bool>() { Daisy::black() } else { Daisy::white() })
+                }
+                None => None,
+            })
+            .collect();
+
+        World::with_daisies(parent.dim, daisies, death_rate, rng.gen::<u64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // chunk0-4's selection/breeding loop should be deterministic under a
+    // fixed seed, and should always have a best world to report.
+    #[test]
+    fn step_generation_is_deterministic_and_tracks_best() {
+        let mut pop_a = Population::new_seeded(6, (6, 6), 99);
+        let mut pop_b = Population::new_seeded(6, (6, 6), 99);
+
+        pop_a.step_generation(5, |w| w.albedo());
+        pop_b.step_generation(5, |w| w.albedo());
+
+        assert_eq!(
+            pop_a.best_world().unwrap().albedo(),
+            pop_b.best_world().unwrap().albedo()
+        );
+    }
+}
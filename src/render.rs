@@ -0,0 +1,52 @@
+use image::{ImageResult, Rgb, RgbImage};
+
+use crate::World;
+
+// blue (cold) to red (hot) heatmap colour for value normalized against (min, max)
+fn heatmap_colour(value: f64, min: f64, max: f64) -> Rgb<u8> {
+    let t = if max > min { ((value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.5 };
+    Rgb([
+        (t * 255.0) as u8,
+        0,
+        ((1.0 - t) * 255.0) as u8,
+    ])
+}
+
+impl World {
+
+    // rasterize the daisy grid as greyscale by albedo: white daisies
+    // light grey, black daisies dark grey, empty cells (0.5) mid-grey
+    pub(crate) fn render_albedo(&self, path: &str) -> ImageResult<()> {
+        let (width, height) = (self.dim.1 as u32, self.dim.0 as u32);
+        let mut img = RgbImage::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                let albedo = self.daisies[i].as_ref().map(|d| d.albedo).unwrap_or(0.5);
+                let shade = (albedo * 255.0) as u8;
+                img.put_pixel(x, y, Rgb([shade, shade, shade]));
+            }
+        }
+
+        img.save(path)
+    }
+
+    // rasterize temperature_field() as a blue-to-red heatmap
+    pub(crate) fn render_temperature(&self, path: &str) -> ImageResult<()> {
+        let (width, height) = (self.dim.1 as u32, self.dim.0 as u32);
+        let tfield = self.temperature_field();
+        let min = tfield.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = tfield.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        let mut img = RgbImage::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                img.put_pixel(x, y, heatmap_colour(tfield[i], min, max));
+            }
+        }
+
+        img.save(path)
+    }
+}